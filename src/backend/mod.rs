@@ -3,14 +3,42 @@
 /// Program structure:
 /// [ [block1] [block2] [block3] [block4] ]
 /// Program counter is always on [index]
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Where [`Context::scratch`] starts handing out temporary cells, well past
+/// any `Var` a caller is realistically tracking by hand.
+const FIRST_SCRATCH: usize = 64;
 
 pub struct Context<'a> {
     pub bf: &'a mut Vec<u8>,
     pub stack: Stack,
+
+    /// The pointer's current absolute tape offset, so the `Var`-based
+    /// methods below can generate whatever `move_right`/`move_left` is
+    /// needed to reach a cell instead of requiring the caller to track it.
+    /// Only those methods update it: raw `move_right`/`move_left` calls
+    /// leave it unchanged.
+    pub position: usize,
+    next_scratch: usize,
 }
 
 pub struct Stack;
 
+/// A handle to a single tape cell, identified by its absolute offset from
+/// the `[index]` cell at position `0`. Plain token emission (`increase`,
+/// `move_right`, ...) doesn't know about `Var`s at all; they're only used
+/// by the higher-level methods below `Context::zero`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Var(usize);
+
+impl Var {
+    /// A handle to the cell at absolute offset `offset`.
+    pub const fn at(offset: usize) -> Self {
+        Self(offset)
+    }
+}
+
 pub fn construct(f: impl FnOnce(&mut Context)) -> Vec<u8> {
     let mut bf = Vec::new();
     let mut context = Context::new(&mut bf);
@@ -21,7 +49,12 @@ pub fn construct(f: impl FnOnce(&mut Context)) -> Vec<u8> {
 
 impl<'a> Context<'a> {
     pub fn new(bf: &'a mut Vec<u8>) -> Self {
-        Self { bf, stack: Stack }
+        Self {
+            bf,
+            stack: Stack,
+            position: 0,
+            next_scratch: FIRST_SCRATCH,
+        }
     }
 
     pub fn increase(&mut self, amount: u8) {
@@ -62,4 +95,165 @@ impl<'a> Context<'a> {
         self.bf.push(b']');
         result
     }
+
+    /// Moves the pointer from `self.position` to `var`, emitting whatever
+    /// `move_right`/`move_left` run that takes, and updates `self.position`.
+    /// A no-op if the pointer is already there.
+    fn goto(&mut self, var: Var) {
+        let target = var.0;
+        if target > self.position {
+            let mut delta = target - self.position;
+            while delta > 0 {
+                let step = delta.min(u8::MAX as usize);
+                self.move_right(step as u8);
+                delta -= step;
+            }
+        } else if target < self.position {
+            let mut delta = self.position - target;
+            while delta > 0 {
+                let step = delta.min(u8::MAX as usize);
+                self.move_left(step as u8);
+                delta -= step;
+            }
+        }
+        self.position = target;
+    }
+
+    /// Hands out a fresh cell beyond any `Var` a caller is expected to be
+    /// using, for `copy`/`mul` to stash intermediate values in.
+    fn scratch(&mut self) -> Var {
+        let var = Var(self.next_scratch);
+        self.next_scratch += 1;
+        var
+    }
+
+    /// Sets `var` to `0`.
+    pub fn zero(&mut self, var: Var) {
+        self.goto(var);
+        self.begin_loop(|ctx| ctx.decrease(1));
+    }
+
+    /// Sets `var` to the literal `value`.
+    pub fn set(&mut self, var: Var, value: u8) {
+        self.zero(var);
+        self.goto(var);
+        self.increase(value);
+    }
+
+    /// `dst += src`, consuming `src` (it ends at `0`). The classic
+    /// temp-free BF add loop: `src [ dst + src - ]`.
+    pub fn add_assign(&mut self, dst: Var, src: Var) {
+        self.goto(src);
+        self.begin_loop(|ctx| {
+            ctx.goto(dst);
+            ctx.increase(1);
+            ctx.goto(src);
+            ctx.decrease(1);
+        });
+    }
+
+    /// `dst += src`, leaving `src` unchanged: the usual add loop, but
+    /// routed through a scratch cell that gets copied back into `src`
+    /// afterwards.
+    fn add_assign_preserving(&mut self, dst: Var, src: Var) {
+        let temp = self.scratch();
+        self.zero(temp);
+        self.goto(src);
+        self.begin_loop(|ctx| {
+            ctx.goto(dst);
+            ctx.increase(1);
+            ctx.goto(temp);
+            ctx.increase(1);
+            ctx.goto(src);
+            ctx.decrease(1);
+        });
+        self.add_assign(src, temp);
+    }
+
+    /// `dst = src`, leaving `src` unchanged.
+    pub fn copy(&mut self, dst: Var, src: Var) {
+        self.zero(dst);
+        self.add_assign_preserving(dst, src);
+    }
+
+    /// `dst = a * b`, leaving `a` and `b` unchanged. Built from
+    /// `add_assign_preserving` and a scratch counter: `dst = 0; counter =
+    /// b; while counter { dst += a (preserving a); counter -= 1 }` — the
+    /// standard repeated-copy BF multiply.
+    pub fn mul(&mut self, dst: Var, a: Var, b: Var) {
+        self.zero(dst);
+        let counter = self.scratch();
+        self.copy(counter, b);
+        self.goto(counter);
+        self.begin_loop(|ctx| {
+            ctx.add_assign_preserving(dst, a);
+            ctx.goto(counter);
+            ctx.decrease(1);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::{Interpreter, Output};
+    use crate::io::BufferIo;
+
+    fn run(bf: &[u8]) -> Interpreter<BufferIo> {
+        let mut interpreter = Interpreter::<BufferIo>::new(bf).unwrap();
+        assert_eq!(interpreter.run(), Err(Output::End));
+        interpreter
+    }
+
+    #[test]
+    fn set_writes_a_literal() {
+        let bf = construct(|ctx| ctx.set(Var::at(1), 42));
+        assert_eq!(run(&bf).tape[1], 42);
+    }
+
+    #[test]
+    fn zero_clears_a_cell() {
+        let bf = construct(|ctx| {
+            ctx.set(Var::at(1), 7);
+            ctx.zero(Var::at(1));
+        });
+        assert_eq!(run(&bf).tape[1], 0);
+    }
+
+    #[test]
+    fn add_assign_consumes_src() {
+        let bf = construct(|ctx| {
+            ctx.set(Var::at(1), 5);
+            ctx.set(Var::at(2), 3);
+            ctx.add_assign(Var::at(1), Var::at(2));
+        });
+        let interpreter = run(&bf);
+        assert_eq!(interpreter.tape[1], 8);
+        assert_eq!(interpreter.tape[2], 0);
+    }
+
+    #[test]
+    fn copy_overwrites_dst_and_preserves_src() {
+        let bf = construct(|ctx| {
+            ctx.set(Var::at(1), 9);
+            ctx.set(Var::at(2), 3);
+            ctx.copy(Var::at(1), Var::at(2));
+        });
+        let interpreter = run(&bf);
+        assert_eq!(interpreter.tape[1], 3);
+        assert_eq!(interpreter.tape[2], 3);
+    }
+
+    #[test]
+    fn mul_preserves_both_operands() {
+        let bf = construct(|ctx| {
+            ctx.set(Var::at(1), 6);
+            ctx.set(Var::at(2), 7);
+            ctx.mul(Var::at(3), Var::at(1), Var::at(2));
+        });
+        let interpreter = run(&bf);
+        assert_eq!(interpreter.tape[3], 42);
+        assert_eq!(interpreter.tape[1], 6);
+        assert_eq!(interpreter.tape[2], 7);
+    }
 }
@@ -0,0 +1,241 @@
+//! Lowers raw Brainfuck source into a `Vec<Op>` once, ahead of execution,
+//! instead of re-discovering loop targets on every pass through `tick`.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// A single resolved instruction. `JumpIfZero`/`JumpIfNonZero` targets are
+/// absolute indices into the surrounding `Vec<Op>`, computed at compile
+/// time so the interpreter never has to search for a matching bracket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    Add(u8),
+    Move(isize),
+    Output,
+    Input,
+    JumpIfZero(usize),
+    JumpIfNonZero(usize),
+    SetZero,
+    ScanRight,
+    ScanLeft,
+}
+
+/// Why a program failed to compile. The offset is the index into the
+/// original source, so callers can point a user at the offending byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompileError {
+    UnbalancedBrackets(usize),
+    UnbalancedComment(usize),
+    UnallowedCharacter(usize),
+}
+
+/// Compiles `bf` into a flat, fused instruction list.
+///
+/// Runs of `+`/`-` collapse into a single `Add` (wrapping), runs of `<`/`>`
+/// collapse into a single `Move`, `{ comment }` blocks are dropped entirely,
+/// and the idioms `[-]`/`[+]` and `[>]`/`[<]` are recognized as `SetZero`
+/// and `ScanRight`/`ScanLeft`. Everything else becomes a `JumpIfZero`/
+/// `JumpIfNonZero` pair with the matching bracket's position baked in.
+pub fn compile(bf: &[u8]) -> Result<Vec<Op>, CompileError> {
+    let clean = strip_comments(bf)?;
+    lower(&clean)
+}
+
+/// A token surviving comment-stripping, paired with its offset in the
+/// original `bf` source so later errors can still point at the real byte.
+type Token = (u8, usize);
+
+fn strip_comments(bf: &[u8]) -> Result<Vec<Token>, CompileError> {
+    let mut out = Vec::with_capacity(bf.len());
+    let mut depth = 0usize;
+    let mut comment_start = 0usize;
+    for (i, &c) in bf.iter().enumerate() {
+        match c {
+            b'{' => {
+                if depth == 0 {
+                    comment_start = i;
+                }
+                depth += 1;
+            }
+            b'}' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or(CompileError::UnbalancedComment(i))?;
+            }
+            _ if depth > 0 => {}
+            b'+' | b'-' | b'<' | b'>' | b'.' | b',' | b'[' | b']' => out.push((c, i)),
+            _ => return Err(CompileError::UnallowedCharacter(i)),
+        }
+    }
+    if depth != 0 {
+        return Err(CompileError::UnbalancedComment(comment_start));
+    }
+    Ok(out)
+}
+
+fn lower(clean: &[Token]) -> Result<Vec<Op>, CompileError> {
+    let mut ops = Vec::new();
+    let mut open_brackets = Vec::new();
+    let mut i = 0;
+
+    while i < clean.len() {
+        let (token, source) = clean[i];
+        match token {
+            b'+' | b'-' => {
+                let mut amount: u8 = 0;
+                while i < clean.len() && matches!(clean[i].0, b'+' | b'-') {
+                    amount = if clean[i].0 == b'+' {
+                        amount.wrapping_add(1)
+                    } else {
+                        amount.wrapping_sub(1)
+                    };
+                    i += 1;
+                }
+                ops.push(Op::Add(amount));
+            }
+            b'<' | b'>' => {
+                let mut amount: isize = 0;
+                while i < clean.len() && matches!(clean[i].0, b'<' | b'>') {
+                    amount += if clean[i].0 == b'>' { 1 } else { -1 };
+                    i += 1;
+                }
+                ops.push(Op::Move(amount));
+            }
+            b'.' => {
+                ops.push(Op::Output);
+                i += 1;
+            }
+            b',' => {
+                ops.push(Op::Input);
+                i += 1;
+            }
+            b'[' => {
+                if let Some((op, next)) = match_idiom(clean, i) {
+                    ops.push(op);
+                    i = next;
+                } else {
+                    open_brackets.push((ops.len(), source));
+                    ops.push(Op::JumpIfZero(0)); // patched once `]` is found
+                    i += 1;
+                }
+            }
+            b']' => {
+                let (open, _) = open_brackets
+                    .pop()
+                    .ok_or(CompileError::UnbalancedBrackets(source))?;
+                let body_start = open + 1;
+                let after_loop = ops.len() + 1;
+                ops[open] = Op::JumpIfZero(after_loop);
+                ops.push(Op::JumpIfNonZero(body_start));
+                i += 1;
+            }
+            _ => unreachable!("strip_comments only lets bf tokens through"),
+        }
+    }
+
+    if let Some((_, source)) = open_brackets.pop() {
+        return Err(CompileError::UnbalancedBrackets(source));
+    }
+
+    Ok(ops)
+}
+
+/// Recognizes `[-]`/`[+]` (zero the current cell) and `[>]`/`[<]` (scan
+/// until a zero cell) as a single fused op, returning the op and the index
+/// just past the closing `]`.
+fn match_idiom(clean: &[Token], i: usize) -> Option<(Op, usize)> {
+    let body = clean.get(i + 1)?.0;
+    if clean.get(i + 2).map(|&(c, _)| c) != Some(b']') {
+        return None;
+    }
+    let op = match body {
+        b'-' | b'+' => Op::SetZero,
+        b'>' => Op::ScanRight,
+        b'<' => Op::ScanLeft,
+        _ => return None,
+    };
+    Some((op, i + 3))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    #[test]
+    fn folds_runs_of_add_and_sub() {
+        assert_eq!(compile(b"+++").unwrap(), vec![Op::Add(3)]);
+        assert_eq!(compile(b"--").unwrap(), vec![Op::Add(254)]);
+        assert_eq!(compile(b"++--+").unwrap(), vec![Op::Add(1)]);
+    }
+
+    #[test]
+    fn folds_runs_of_move() {
+        assert_eq!(compile(b">>>").unwrap(), vec![Op::Move(3)]);
+        assert_eq!(compile(b"<<").unwrap(), vec![Op::Move(-2)]);
+        assert_eq!(compile(b"><><<").unwrap(), vec![Op::Move(-1)]);
+    }
+
+    #[test]
+    fn recognizes_set_zero_idiom() {
+        assert_eq!(compile(b"[-]").unwrap(), vec![Op::SetZero]);
+        assert_eq!(compile(b"[+]").unwrap(), vec![Op::SetZero]);
+    }
+
+    #[test]
+    fn recognizes_scan_idioms() {
+        assert_eq!(compile(b"[>]").unwrap(), vec![Op::ScanRight]);
+        assert_eq!(compile(b"[<]").unwrap(), vec![Op::ScanLeft]);
+    }
+
+    #[test]
+    fn resolves_ordinary_loop_targets() {
+        let ops = compile(b"+[>+<-]").unwrap();
+        assert_eq!(
+            ops,
+            vec![
+                Op::Add(1),
+                Op::JumpIfZero(7),
+                Op::Move(1),
+                Op::Add(1),
+                Op::Move(-1),
+                Op::Add(255),
+                Op::JumpIfNonZero(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_comments() {
+        assert_eq!(compile(b"+{ this is a comment [ }-").unwrap(), vec![Op::Add(0)]);
+    }
+
+    #[test]
+    fn rejects_unbalanced_brackets() {
+        assert_eq!(compile(b"[+"), Err(CompileError::UnbalancedBrackets(0)));
+        assert_eq!(compile(b"+]"), Err(CompileError::UnbalancedBrackets(1)));
+    }
+
+    #[test]
+    fn unbalanced_bracket_offset_survives_rle_and_comment_stripping() {
+        // the real `[` is at source byte 2, not ops-index 1
+        assert_eq!(compile(b"++[+"), Err(CompileError::UnbalancedBrackets(2)));
+        // the real `[` is at source byte 9, past the stripped `{comment}`
+        assert_eq!(
+            compile(b"{comment}[+"),
+            Err(CompileError::UnbalancedBrackets(9))
+        );
+    }
+
+    #[test]
+    fn rejects_unbalanced_comments() {
+        assert_eq!(compile(b"{+"), Err(CompileError::UnbalancedComment(0)));
+        assert_eq!(compile(b"+}"), Err(CompileError::UnbalancedComment(1)));
+    }
+
+    #[test]
+    fn rejects_unallowed_characters() {
+        assert_eq!(compile(b"+@-"), Err(CompileError::UnallowedCharacter(1)));
+    }
+}
@@ -1,50 +1,71 @@
-use std::collections::{HashMap, VecDeque};
-use std::ops::Index;
-use std::range::Range;
+use core::ops::Index;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::compiler::{self, CompileError, Op};
+use crate::io::{AsyncIo, Io};
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Interpreter<'a> {
-    pub bf: &'a [u8],
+pub struct Interpreter<IO> {
+    pub ops: Vec<Op>,
     pub pc: usize,
-    pub cache: HashMap<usize, usize>,
 
     pub tape: Vec<u8>,
     pub location: usize,
 
-    pub input: VecDeque<u8>,
+    pub io: IO,
 
     pub history: Vec<Delta>,
     pub keep_history: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
-pub enum Delta {
-    Add(u8),
-    Sub(u8),
+/// A reversible record of one `tick`: the `pc` it executed from, plus
+/// whatever state it clobbered. `pc` alone is enough to unwind control flow
+/// (straight-line steps and taken jumps both just restore the prior `pc`);
+/// `change` carries whatever else needs restoring.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub pc: usize,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// the byte at `index` was overwritten; it used to be `previous`.
+    Write { index: usize, previous: u8 },
+    /// `location` moved by `amount`.
     Move(isize),
-    Jump(usize),
+    /// only `pc` changed (a jump, taken or not, or `.`).
+    None,
 }
 
 #[derive(Debug, Clone, PartialEq, Hash)]
 pub enum Output {
     RequiresInput,
     End,
-    UnallowedCharacter(usize),
     TriedToMoveOutOfBounds,
 }
 
-impl<'a> Interpreter<'a> {
-    pub fn new(bf: &'a [u8]) -> Self {
-        Self {
-            bf,
+impl<IO: Default> Interpreter<IO> {
+    pub fn new(bf: &[u8]) -> Result<Self, CompileError> {
+        Self::with_io(bf, IO::default())
+    }
+}
+
+impl<IO> Interpreter<IO> {
+    pub fn with_io(bf: &[u8], io: IO) -> Result<Self, CompileError> {
+        Ok(Self {
+            ops: compiler::compile(bf)?,
             pc: 0,
-            cache: HashMap::new(),
             tape: vec![0; 30_000],
             location: 0,
-            input: VecDeque::new(),
+            io,
             history: Vec::new(),
             keep_history: false,
-        }
+        })
     }
 
     pub fn keep_history(&mut self) -> &mut Self {
@@ -58,49 +79,33 @@ impl<'a> Interpreter<'a> {
         self
     }
 
-    pub fn run(&mut self) -> Result<u8, Output> {
-        while self.pc < self.bf.len() {
-            match self.tick() {
-                Ok(Some(byte)) => return Ok(byte),
-                Ok(None) => continue,
-                Err(e) => return Err(e),
-            }
+    /// Undoes the last recorded `tick`, restoring `pc` and whatever tape
+    /// cell or `location` it changed. A no-op if `history` is empty, which
+    /// includes the case where `keep_history` was never turned on.
+    pub fn step_back(&mut self) -> Result<(), Output> {
+        let Some(delta) = self.history.pop() else {
+            return Ok(());
+        };
+        match delta.change {
+            Change::Write { index, previous } => *self.cell(index) = previous,
+            Change::Move(amount) => self.location = (self.location as isize - amount) as usize,
+            Change::None => {}
         }
-        Err(Output::End)
+        self.pc = delta.pc;
+        Ok(())
     }
 
-    pub fn run_steps(&mut self, mut steps: usize) -> Result<Option<u8>, Output> {
-        while self.pc < self.bf.len() && steps > 0 {
-            match self.tick() {
-                Ok(Some(byte)) => return Ok(Some(byte)),
-                Ok(None) => steps -= 1,
-                Err(e) => return Err(e),
-            }
-        }
-        if steps == 0 {
-            return Ok(None);
+    /// Calls [`Self::step_back`] `n` times.
+    pub fn rewind(&mut self, n: usize) -> Result<(), Output> {
+        for _ in 0..n {
+            self.step_back()?;
         }
-        Err(Output::End)
+        Ok(())
     }
 
-    pub fn tick(&mut self) -> Result<Option<u8>, Output> {
-        if self.pc >= self.bf.len() {
-            return Err(Output::End);
-        }
-
-        let command = self.bf[self.pc];
-
-        match command {
-            b'>' => self.move_right(),
-            b'<' => self.move_left(),
-            b'+' => self.add(),
-            b'-' => self.sub(),
-            b'.' => self.output(),
-            b',' => self.input(),
-            b'[' => self.jump_forward(),
-            b']' => self.jump_back(),
-            b'{' => self.comment(),
-            _ => Err(Output::UnallowedCharacter(self.pc)),
+    fn record(&mut self, change: Change) {
+        if self.keep_history {
+            self.history.push(Delta { pc: self.pc, change });
         }
     }
 
@@ -118,263 +123,336 @@ impl<'a> Interpreter<'a> {
         &self.tape[indeces]
     }
 
-    fn move_right(&mut self) -> Result<Option<u8>, Output> {
+    fn add(&mut self, amount: u8) -> Result<(), Output> {
+        let index = self.location;
+        let previous = *self.cell(index);
+        self.record(Change::Write { index, previous });
         self.pc += 1;
-        self.location += 1;
-        if self.keep_history {
-            self.history.push(Delta::Move(1));
-        }
-        Ok(None)
+        *self.cell(index) = previous.wrapping_add(amount);
+        Ok(())
     }
 
-    fn move_left(&mut self) -> Result<Option<u8>, Output> {
-        if self.location == 0 {
+    fn move_by(&mut self, amount: isize) -> Result<(), Output> {
+        let target = self.location as isize + amount;
+        if target < 0 {
             return Err(Output::TriedToMoveOutOfBounds);
         }
+        self.record(Change::Move(amount));
         self.pc += 1;
-        self.location -= 1;
-        if self.keep_history {
-            self.history.push(Delta::Move(-1));
-        }
-        Ok(None)
+        self.location = target as usize;
+        Ok(())
     }
 
-    fn add(&mut self) -> Result<Option<u8>, Output> {
-        self.pc += 1;
-        *self.cell(self.location) = self.cell(self.location).wrapping_add(1);
-        if self.keep_history {
-            self.history.push(Delta::Add(1));
-        }
-        Ok(None)
+    fn jump_if_zero(&mut self, target: usize) -> Result<(), Output> {
+        self.record(Change::None);
+        self.pc = if *self.cell(self.location) == 0 {
+            target
+        } else {
+            self.pc + 1
+        };
+        Ok(())
     }
 
-    fn sub(&mut self) -> Result<Option<u8>, Output> {
-        self.pc += 1;
-        *self.cell(self.location) = self.cell(self.location).wrapping_sub(1);
-        if self.keep_history {
-            self.history.push(Delta::Sub(1));
-        }
-        Ok(None)
+    fn jump_if_non_zero(&mut self, target: usize) -> Result<(), Output> {
+        self.record(Change::None);
+        self.pc = if *self.cell(self.location) != 0 {
+            target
+        } else {
+            self.pc + 1
+        };
+        Ok(())
     }
 
-    fn output(&mut self) -> Result<Option<u8>, Output> {
+    fn set_zero(&mut self) -> Result<(), Output> {
+        let index = self.location;
+        let previous = *self.cell(index);
+        self.record(Change::Write { index, previous });
         self.pc += 1;
-        if self.keep_history {
-            self.history.push(Delta::Add(0));
-        }
-        Ok(Some(*self.cell(self.location)))
+        *self.cell(index) = 0;
+        Ok(())
     }
 
-    fn input(&mut self) -> Result<Option<u8>, Output> {
-        self.pc += 1;
-        if let Some(byte) = self.input.pop_front() {
-            *self.cell(self.location) = byte;
-            if self.keep_history {
-                self.history.push(Delta::Add(byte));
+    /// Moves by `direction` (`1` or `-1`) until the current cell is zero,
+    /// the fused form of the `[>]`/`[<]` idiom.
+    fn scan(&mut self, direction: isize) -> Result<(), Output> {
+        let start = self.location;
+        while *self.cell(self.location) != 0 {
+            let next = self.location as isize + direction;
+            if next < 0 {
+                return Err(Output::TriedToMoveOutOfBounds);
             }
-            Ok(None)
-        } else {
-            Err(Output::RequiresInput)
+            self.location = next as usize;
         }
+        self.record(Change::Move(self.location as isize - start as isize));
+        self.pc += 1;
+        Ok(())
     }
 
-    /// if the current cell is 0, jump to the matching ]
-    fn jump_forward(&mut self) -> Result<Option<u8>, Output> {
-        if *self.cell(self.location) != 0 {
-            self.pc += 1;
-            return Ok(None);
-        }
-        if let Some(&jump) = self.cache.get(&self.pc) {
-            self.pc = jump;
-            if self.keep_history {
-                self.history.push(Delta::Jump(self.pc));
-            }
-            return Ok(None);
-        }
-        let mut depth = 1;
-        let jump = self.pc;
-        while depth > 0 {
-            self.pc += 1;
-            if self.pc >= self.bf.len() {
-                return Err(Output::TriedToMoveOutOfBounds);
-            }
-            match self.bf[self.pc] {
-                b'[' => depth += 1,
-                b']' => depth -= 1,
-                _ => {}
-            }
-        }
+    /// Reads the current cell for an `Output` op, advancing `pc`. Returns
+    /// the byte so the caller can hand it to whichever `Io`/`AsyncIo` it's
+    /// driving.
+    fn begin_output(&mut self) -> u8 {
+        self.record(Change::None);
+        let byte = *self.cell(self.location);
         self.pc += 1;
-        self.cache.insert(jump, self.pc);
-        if self.keep_history {
-            self.history.push(Delta::Jump(self.pc));
-        }
-        Ok(None)
+        byte
     }
 
-    fn jump_back(&mut self) -> Result<Option<u8>, Output> {
-        if *self.cell(self.location) == 0 {
-            self.pc += 1;
-            return Ok(None);
-        }
-        if let Some(&jump) = self.cache.get(&self.pc) {
-            self.pc = jump;
-            if self.keep_history {
-                self.history.push(Delta::Jump(self.pc));
-            }
-            return Ok(None);
-        }
-        let mut depth = 1;
-        let jump = self.pc;
-        while depth > 0 {
-            self.pc -= 1;
-            if self.pc == 0 {
-                return Err(Output::TriedToMoveOutOfBounds);
-            }
-            match self.bf[self.pc] {
-                b']' => depth += 1,
-                b'[' => depth -= 1,
-                _ => {}
-            }
-        }
-        self.cache.insert(jump, self.pc);
-        if self.keep_history {
-            self.history.push(Delta::Jump(self.pc));
+    /// Writes `byte` into the current cell for an `Input` op, advancing
+    /// `pc`. The caller is responsible for having obtained `byte` from the
+    /// backend first.
+    fn begin_input(&mut self, byte: u8) {
+        let index = self.location;
+        let previous = *self.cell(index);
+        self.record(Change::Write { index, previous });
+        self.pc += 1;
+        *self.cell(index) = byte;
+    }
+}
+
+impl<IO: Io> Interpreter<IO> {
+    pub fn run(&mut self) -> Result<(), Output> {
+        while self.pc < self.ops.len() {
+            self.tick()?;
         }
-        Ok(None)
+        Err(Output::End)
     }
 
-    fn comment(&mut self) -> Result<Option<u8>, Output> {
-        if let Some(jump) = self.cache.get(&self.pc) {
-            self.pc = *jump;
-            return Ok(None);
+    pub fn run_steps(&mut self, mut steps: usize) -> Result<(), Output> {
+        while self.pc < self.ops.len() && steps > 0 {
+            self.tick()?;
+            steps -= 1;
         }
-        let mut depth = 1;
-        let jump = self.pc;
-        while depth > 0 {
-            self.pc += 1;
-            if self.pc >= self.bf.len() {
-                return Err(Output::TriedToMoveOutOfBounds);
-            }
-            match self.bf[self.pc] {
-                b'{' => depth += 1,
-                b'}' => depth -= 1,
-                _ => {}
-            }
+        if steps == 0 {
+            return Ok(());
         }
-        self.pc += 1;
-        self.cache.insert(jump, self.pc);
-        if self.keep_history {
-            self.history.push(Delta::Jump(self.pc));
+        Err(Output::End)
+    }
+
+    pub fn tick(&mut self) -> Result<(), Output> {
+        let Some(op) = self.ops.get(self.pc).copied() else {
+            return Err(Output::End);
+        };
+
+        match op {
+            Op::Add(amount) => self.add(amount),
+            Op::Move(amount) => self.move_by(amount),
+            Op::Output => self.output(),
+            Op::Input => self.input(),
+            Op::JumpIfZero(target) => self.jump_if_zero(target),
+            Op::JumpIfNonZero(target) => self.jump_if_non_zero(target),
+            Op::SetZero => self.set_zero(),
+            Op::ScanRight => self.scan(1),
+            Op::ScanLeft => self.scan(-1),
         }
-        Ok(None)
     }
 
-    pub fn load_input(&mut self, input: &[u8]) {
-        self.input.extend(input.iter());
+    fn output(&mut self) -> Result<(), Output> {
+        let byte = self.begin_output();
+        self.io.write_byte(byte);
+        Ok(())
     }
 
-    pub fn take_output(&mut self, output: &mut [u8]) -> Result<usize, Output> {
-        for i in 0..output.len() {
-            match self.run() {
-                Ok(byte) => output[i] = byte,
-                Err(Output::End) => return Ok(i + 1),
-                Err(o) => return Err(o),
+    fn input(&mut self) -> Result<(), Output> {
+        match self.io.read_byte() {
+            Some(byte) => {
+                self.begin_input(byte);
+                Ok(())
             }
+            None => Err(Output::RequiresInput),
+        }
+    }
+}
+
+impl<IO: AsyncIo> Interpreter<IO> {
+    /// The `AsyncIo` counterpart of [`Interpreter::tick`]: identical except
+    /// `Input` suspends while `AsyncIo::poll_read` is `Pending` instead of
+    /// failing with `RequiresInput`, so an event loop can await more data
+    /// arriving. A `Ready(None)` from `poll_read` still ends in `RequiresInput`.
+    pub async fn tick_async(&mut self) -> Result<(), Output> {
+        let Some(op) = self.ops.get(self.pc).copied() else {
+            return Err(Output::End);
+        };
+
+        match op {
+            Op::Add(amount) => self.add(amount),
+            Op::Move(amount) => self.move_by(amount),
+            Op::Output => {
+                let byte = self.begin_output();
+                self.io.write_byte(byte);
+                Ok(())
+            }
+            Op::Input => match core::future::poll_fn(|cx| self.io.poll_read(cx)).await {
+                Some(byte) => {
+                    self.begin_input(byte);
+                    Ok(())
+                }
+                None => Err(Output::RequiresInput),
+            },
+            Op::JumpIfZero(target) => self.jump_if_zero(target),
+            Op::JumpIfNonZero(target) => self.jump_if_non_zero(target),
+            Op::SetZero => self.set_zero(),
+            Op::ScanRight => self.scan(1),
+            Op::ScanLeft => self.scan(-1),
         }
-        Ok(output.len())
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::io::BufferIo;
 
     #[test]
     fn inc() {
-        let mut interpreter = Interpreter::new(b"+");
+        let mut interpreter = Interpreter::<BufferIo>::new(b"+").unwrap();
         assert_eq!(interpreter.run(), Err(Output::End));
         assert_eq!(interpreter.tape[0], 1);
     }
 
     #[test]
     fn dec() {
-        let mut interpreter = Interpreter::new(b"-");
+        let mut interpreter = Interpreter::<BufferIo>::new(b"-").unwrap();
         assert_eq!(interpreter.run(), Err(Output::End));
         assert_eq!(interpreter.tape[0], 255);
     }
 
     #[test]
     fn r#move() {
-        let mut interpreter = Interpreter::new(b">");
+        let mut interpreter = Interpreter::<BufferIo>::new(b">").unwrap();
         assert_eq!(interpreter.run(), Err(Output::End));
         assert_eq!(interpreter.location, 1);
     }
 
     #[test]
     fn move_back() {
-        let mut interpreter = Interpreter::new(b"><");
+        let mut interpreter = Interpreter::<BufferIo>::new(b"><").unwrap();
         assert_eq!(interpreter.run(), Err(Output::End));
         assert_eq!(interpreter.location, 0);
     }
 
     #[test]
     fn output() {
-        let mut interpreter = Interpreter::new(b".");
+        let mut interpreter = Interpreter::<BufferIo>::new(b".").unwrap();
         interpreter.tape[0] = 65; // ASCII for 'A'
-        assert_eq!(interpreter.run(), Ok(65));
+        assert_eq!(interpreter.run(), Err(Output::End));
+        assert_eq!(interpreter.io.output, vec![65]);
     }
 
     #[test]
     fn input() {
-        let mut interpreter = Interpreter::new(b",");
-        interpreter.input.push_back(65); // ASCII for 'A'
+        let mut interpreter =
+            Interpreter::with_io(b",", BufferIo::with_input(&[65])).unwrap(); // ASCII for 'A'
         assert_eq!(interpreter.run(), Err(Output::End));
         assert_eq!(interpreter.tape[0], 65);
     }
 
+    #[test]
+    fn input_without_a_byte_ready_requires_input() {
+        let mut interpreter = Interpreter::<BufferIo>::new(b",").unwrap();
+        assert_eq!(interpreter.run(), Err(Output::RequiresInput));
+    }
+
     #[test]
     fn jump_forward() {
-        let mut interpreter = Interpreter::new(b"[+]");
+        // `[+>]` is not a fused idiom, so it stays a real JumpIfZero
+        let mut interpreter = Interpreter::<BufferIo>::new(b"[+>]").unwrap();
         assert_eq!(interpreter.run_steps(100), Err(Output::End));
         assert_eq!(interpreter.tape[0], 0);
     }
 
     #[test]
     fn jump_back() {
-        let mut interpreter = Interpreter::new(b"+++++[-]");
+        // `[->+<]` is not a fused idiom, so it loops through a real JumpIfNonZero
+        let mut interpreter = Interpreter::<BufferIo>::new(b"+++++[->+<]").unwrap();
         assert_eq!(interpreter.run(), Err(Output::End));
         assert_eq!(interpreter.tape[0], 0);
     }
 
     #[test]
     fn move_out_of_bounds() {
-        let mut interpreter = Interpreter::new(b"<");
-        interpreter.location = 0;
-        assert_eq!(interpreter.move_left(), Err(Output::TriedToMoveOutOfBounds));
+        let mut interpreter = Interpreter::<BufferIo>::new(b"<").unwrap();
+        assert_eq!(interpreter.run(), Err(Output::TriedToMoveOutOfBounds));
     }
 
     #[test]
-    fn unallowed_character() {
-        let mut interpreter = Interpreter::new(b"@");
-        assert_eq!(interpreter.run(), Err(Output::UnallowedCharacter(0)));
+    fn unallowed_character_is_a_compile_error() {
+        assert_eq!(
+            Interpreter::<BufferIo>::new(b"@").unwrap_err(),
+            CompileError::UnallowedCharacter(0)
+        );
     }
 
     #[test]
     fn comment() {
-        let mut interpreter = Interpreter::new(b"{+}");
+        let mut interpreter = Interpreter::<BufferIo>::new(b"{+}").unwrap();
         assert_eq!(interpreter.run(), Err(Output::End));
         assert_eq!(interpreter.tape[0], 0);
     }
 
+    #[test]
+    fn step_back_undoes_add() {
+        let mut interpreter = Interpreter::<BufferIo>::new(b"+").unwrap();
+        interpreter.keep_history();
+        interpreter.run_steps(1).unwrap();
+        assert_eq!(interpreter.tape[0], 1);
+        interpreter.step_back().unwrap();
+        assert_eq!(interpreter.tape[0], 0);
+        assert_eq!(interpreter.pc, 0);
+    }
+
+    #[test]
+    fn step_back_undoes_move() {
+        let mut interpreter = Interpreter::<BufferIo>::new(b">").unwrap();
+        interpreter.keep_history();
+        interpreter.run_steps(1).unwrap();
+        assert_eq!(interpreter.location, 1);
+        interpreter.step_back().unwrap();
+        assert_eq!(interpreter.location, 0);
+        assert_eq!(interpreter.pc, 0);
+    }
+
+    #[test]
+    fn step_back_restores_pc_across_a_taken_jump() {
+        // `[->]` doesn't match the `[-]`/`[+]`/`[>]`/`[<]` idioms, so it
+        // stays a real loop: JumpIfZero(4), Add(255), Move(1), JumpIfNonZero(1).
+        let mut interpreter = Interpreter::<BufferIo>::new(b"[->]+").unwrap();
+        interpreter.keep_history();
+        interpreter.run_steps(1).unwrap(); // tape[0] is 0, so the jump is taken
+        assert_eq!(interpreter.pc, 4);
+        interpreter.step_back().unwrap();
+        assert_eq!(interpreter.pc, 0);
+    }
+
+    #[test]
+    fn rewind_replays_several_steps_in_reverse() {
+        // `+>+` compiles to three ops: Add(1), Move(1), Add(1).
+        let mut interpreter = Interpreter::<BufferIo>::new(b"+>+").unwrap();
+        interpreter.keep_history();
+        interpreter.run_steps(3).unwrap();
+        assert_eq!(interpreter.tape[0], 1);
+        assert_eq!(interpreter.tape[1], 1);
+        interpreter.rewind(3).unwrap();
+        assert_eq!(interpreter.tape[0], 0);
+        assert_eq!(interpreter.tape[1], 0);
+        assert_eq!(interpreter.location, 0);
+        assert_eq!(interpreter.pc, 0);
+    }
+
+    #[test]
+    fn step_back_on_empty_history_is_a_noop() {
+        let mut interpreter = Interpreter::<BufferIo>::new(b"+").unwrap();
+        assert_eq!(interpreter.step_back(), Ok(()));
+        assert_eq!(interpreter.pc, 0);
+    }
+
     #[test]
     fn hello_world() {
-        let mut interpreter = Interpreter::new(b"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.");
-        let mut output = [0; 13];
+        let mut interpreter = Interpreter::<BufferIo>::new(b"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.").unwrap();
+        assert_eq!(interpreter.run(), Err(Output::End));
         assert_eq!(
-            interpreter.take_output(&mut output),
-            Ok(b"Hello World!\n".len())
+            str::from_utf8(&interpreter.io.output).unwrap(),
+            "Hello World!\n"
         );
-        assert_eq!(str::from_utf8(&output).unwrap(), "Hello World!\n");
     }
 }
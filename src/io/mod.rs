@@ -0,0 +1,169 @@
+//! I/O backends for [`crate::interpreter::Interpreter`]. Input used to be a
+//! bare `VecDeque<u8>` you had to pre-fill and output only escaped through
+//! `run`'s return value, which left no room for real stdin/stdout or for
+//! driving the interpreter from an event loop. [`Io`] and [`AsyncIo`] pull
+//! that decision out of `Interpreter` and into a pluggable backend.
+
+use core::task::{Context, Poll};
+
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+#[cfg(not(feature = "std"))]
+use alloc::collections::VecDeque;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// A synchronous backend: `,` reads through `read_byte`, `.` writes through
+/// `write_byte`. Returning `None` from `read_byte` means "no byte is ready
+/// right now" — what that means (a hard error, or something to retry after
+/// feeding more input) is up to the backend and the caller driving it.
+pub trait Io {
+    fn read_byte(&mut self) -> Option<u8>;
+    fn write_byte(&mut self, byte: u8);
+}
+
+/// The same contract as [`Io`], but `,` suspends instead of giving up when
+/// no byte is ready yet, so an `Interpreter` can be driven from an event
+/// loop via `Interpreter::tick_async`.
+pub trait AsyncIo {
+    fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Option<u8>>;
+    fn write_byte(&mut self, byte: u8);
+}
+
+/// Blocking real stdin/stdout, one byte at a time. Needs `std`: there's no
+/// stdin/stdout without an OS underneath, so this is the one `Io`
+/// implementor not available in a `no_std` build.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct StdIo;
+
+#[cfg(feature = "std")]
+impl Io for StdIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        use std::io::Read;
+        let mut byte = [0u8; 1];
+        std::io::stdin().read_exact(&mut byte).ok()?;
+        Some(byte[0])
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        use std::io::Write;
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(&[byte]);
+        let _ = stdout.flush();
+    }
+}
+
+/// An in-memory backend: input is pre-loaded and output is collected for
+/// later inspection. This is the interpreter's previous built-in behavior,
+/// now just one `Io` implementor among several.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BufferIo {
+    pub input: VecDeque<u8>,
+    pub output: Vec<u8>,
+}
+
+impl BufferIo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_input(input: &[u8]) -> Self {
+        Self {
+            input: input.iter().copied().collect(),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn load_input(&mut self, input: &[u8]) {
+        self.input.extend(input.iter());
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.output)
+    }
+}
+
+impl Io for BufferIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.output.push(byte);
+    }
+}
+
+/// The [`AsyncIo`] counterpart of [`BufferIo`]. Input is always immediately
+/// available or immediately exhausted, so `poll_read` never returns
+/// `Poll::Pending` on its own — but it gives an event loop something real
+/// to `poll_fn` against, and a backend with genuinely async input (a socket,
+/// a channel) can implement the same trait.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AsyncBufferIo {
+    pub input: VecDeque<u8>,
+    pub output: Vec<u8>,
+}
+
+impl AsyncBufferIo {
+    pub fn with_input(input: &[u8]) -> Self {
+        Self {
+            input: input.iter().copied().collect(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl AsyncIo for AsyncBufferIo {
+    fn poll_read(&mut self, _cx: &mut Context<'_>) -> Poll<Option<u8>> {
+        Poll::Ready(self.input.pop_front())
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.output.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buffer_io_reads_then_runs_dry() {
+        let mut io = BufferIo::with_input(&[1, 2]);
+        assert_eq!(io.read_byte(), Some(1));
+        assert_eq!(io.read_byte(), Some(2));
+        assert_eq!(io.read_byte(), None);
+    }
+
+    #[test]
+    fn buffer_io_collects_output() {
+        let mut io = BufferIo::new();
+        io.write_byte(b'H');
+        io.write_byte(b'i');
+        assert_eq!(io.take_output(), b"Hi");
+        assert_eq!(io.output, Vec::new());
+    }
+
+    #[test]
+    fn async_buffer_io_is_always_ready() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut io = AsyncBufferIo::with_input(&[7]);
+        assert_eq!(io.poll_read(&mut cx), Poll::Ready(Some(7)));
+        assert_eq!(io.poll_read(&mut cx), Poll::Ready(None));
+    }
+
+    fn noop_waker() -> core::task::Waker {
+        use core::task::{RawWaker, RawWakerVTable, Waker};
+
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(core::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) }
+    }
+}
@@ -0,0 +1,14 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Interpreting and compiling a program only ever needs `alloc`; `std` is
+// pulled in for stdin/stdout-backed `Io` and the `rustyline` REPL, both of
+// which are gated behind the `std` feature below.
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+pub mod backend;
+pub mod compiler;
+pub mod interpreter;
+pub mod io;
+#[cfg(feature = "std")]
+pub mod repl;
@@ -1,26 +1,14 @@
-use bf::interpreter::*;
-use std::hint::black_box;
-use std::io::{Write, stdout};
+use bf::interpreter::{Interpreter, Output};
+use bf::io::StdIo;
 
 fn main() {
-    let program = b"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>?.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+    let program = b"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
 
-    let mut inter = Interpreter::new().with_str(program).unwrap();
+    let mut interpreter = Interpreter::with_io(program, StdIo).expect("valid program");
 
-    let input = b"\0";
-
-    loop {
-        match inter.poll() {
-            Output::Output(c) => {
-                print!("{}", c as char);
-                stdout().flush().unwrap();
-            }
-            Output::Input => {
-                inter.input(input);
-            }
-            Output::End => {
-                break;
-            }
-        }
+    match interpreter.run() {
+        Err(Output::End) => {}
+        Err(error) => eprintln!("{error:?}"),
+        Ok(()) => unreachable!("run only returns once the program ends or errors"),
     }
 }
@@ -0,0 +1,348 @@
+//! An interactive stepping debugger, built the way the `rustyline`-based
+//! REPLs in the matrix sources are: a small `Helper` bundle (validator +
+//! highlighter) wired into an `Editor`, plus debugger commands layered on
+//! top of the interpreter's existing `tick`/`step_back`.
+//!
+//! Plain lines are Brainfuck source, appended to the running program. The
+//! validator holds a line back while its `[ ]`/`{ }` nesting is still open
+//! and rejects a stray closer, so a multi-line loop can be typed across
+//! several prompts.
+//! Lines starting with `:` are debugger commands: `:break <pc>`, `:step`,
+//! `:continue`, `:back`, `:run`, and `:tape [radius]`.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Editor, Helper, error::ReadlineError, history::DefaultHistory};
+
+use crate::compiler::{self, CompileError, Op};
+use crate::interpreter::{Interpreter, Output};
+use crate::io::Io;
+
+/// An `Interpreter` plus the breakpoints a REPL session sets on it.
+pub struct Debugger<IO> {
+    pub interpreter: Interpreter<IO>,
+    pub breakpoints: HashSet<usize>,
+}
+
+impl<IO: Default> Debugger<IO> {
+    pub fn new(bf: &[u8]) -> Result<Self, CompileError> {
+        Ok(Self {
+            interpreter: Interpreter::new(bf)?,
+            breakpoints: HashSet::new(),
+        })
+    }
+}
+
+impl<IO> Debugger<IO> {
+    pub fn break_at(&mut self, pc: usize) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn clear_break(&mut self, pc: usize) {
+        self.breakpoints.remove(&pc);
+    }
+
+    /// Appends `bf` to the program being debugged. The new ops are compiled
+    /// on their own, so their jump targets start from `0`; they're rebased
+    /// onto the end of the existing program before being spliced in.
+    pub fn extend(&mut self, bf: &[u8]) -> Result<(), CompileError> {
+        let base = self.interpreter.ops.len();
+        let mut new_ops = compiler::compile(bf)?;
+        for op in &mut new_ops {
+            match op {
+                Op::JumpIfZero(target) | Op::JumpIfNonZero(target) => *target += base,
+                _ => {}
+            }
+        }
+        self.interpreter.ops.append(&mut new_ops);
+        Ok(())
+    }
+
+    /// Renders a window of `radius` cells on either side of `location`,
+    /// with the current cell marked.
+    pub fn tape_view(&self, radius: usize) -> String {
+        let len = self.interpreter.tape.len();
+        let location = self.interpreter.location;
+        let width = 2 * radius + 1;
+
+        let mut start = location.saturating_sub(radius);
+        let mut end = (location + radius + 1).min(len);
+
+        // One side may have clamped against the tape's edge; widen the
+        // other side to keep the window `width` cells wide where possible.
+        if end - start < width {
+            if start == 0 {
+                end = width.min(len);
+            } else if end == len {
+                start = len.saturating_sub(width);
+            }
+        }
+
+        self.interpreter
+            .cells(start..end)
+            .iter()
+            .enumerate()
+            .map(|(offset, byte)| {
+                if start + offset == location {
+                    format!("[{byte}]")
+                } else {
+                    format!(" {byte} ")
+                }
+            })
+            .collect()
+    }
+}
+
+impl<IO: Io> Debugger<IO> {
+    pub fn step(&mut self) -> Result<(), Output> {
+        self.interpreter.keep_history();
+        self.interpreter.tick()
+    }
+
+    /// Steps until a breakpoint is reached (after at least one step) or the
+    /// program ends.
+    pub fn continue_(&mut self) -> Result<(), Output> {
+        self.interpreter.keep_history();
+        loop {
+            self.interpreter.tick()?;
+            if self.breakpoints.contains(&self.interpreter.pc) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs to completion, ignoring breakpoints.
+    pub fn run(&mut self) -> Result<(), Output> {
+        self.interpreter.run()
+    }
+}
+
+/// The `rustyline` helper bundle: a validator that holds a line back until
+/// its brackets balance, and a highlighter that colors the eight BF
+/// commands distinctly from `{ comment }` text.
+#[derive(Default)]
+pub struct BfHelper;
+
+impl Helper for BfHelper {}
+
+impl Completer for BfHelper {
+    type Candidate = String;
+}
+
+impl Hinter for BfHelper {
+    type Hint = String;
+}
+
+impl Highlighter for BfHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let mut out = String::with_capacity(line.len());
+        let mut comment_depth = 0u32;
+        for c in line.chars() {
+            let color = match c {
+                '{' => {
+                    comment_depth += 1;
+                    "90"
+                }
+                '}' => {
+                    comment_depth = comment_depth.saturating_sub(1);
+                    "90"
+                }
+                _ if comment_depth > 0 => "90",
+                '+' | '-' => "32",
+                '<' | '>' => "34",
+                '.' | ',' => "35",
+                '[' | ']' => "33",
+                _ => {
+                    out.push(c);
+                    continue;
+                }
+            };
+            out.push_str(&format!("\x1b[{color}m{c}\x1b[0m"));
+        }
+        Cow::Owned(out)
+    }
+
+    fn highlight_char(&self, line: &str, _pos: usize, _forced: bool) -> bool {
+        !line.is_empty()
+    }
+}
+
+impl Validator for BfHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input();
+        if input.starts_with(':') {
+            return Ok(ValidationResult::Valid(None));
+        }
+        Ok(match brackets_balance(input) {
+            Balance::Balanced => ValidationResult::Valid(None),
+            Balance::Open => ValidationResult::Incomplete,
+            Balance::ClosedTooEarly => {
+                ValidationResult::Invalid(Some(" (unmatched closing bracket)".to_string()))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Balance {
+    Balanced,
+    Open,
+    ClosedTooEarly,
+}
+
+/// Checks `[ ]` and `{ }` nesting with separate counts, the same pairs
+/// `compiler::compile` requires to balance; like `compile`, it skips
+/// `[ ]` inside a `{ }` comment.
+fn brackets_balance(input: &str) -> Balance {
+    let mut loops = 0i32;
+    let mut comments = 0i32;
+    for c in input.chars() {
+        match c {
+            '[' if comments == 0 => loops += 1,
+            ']' if comments == 0 => loops -= 1,
+            '{' => comments += 1,
+            '}' => comments -= 1,
+            _ => {}
+        }
+        if loops < 0 || comments < 0 {
+            return Balance::ClosedTooEarly;
+        }
+    }
+    if loops > 0 || comments > 0 {
+        Balance::Open
+    } else {
+        Balance::Balanced
+    }
+}
+
+/// Runs the debugger REPL on stdin/stdout until the user exits (Ctrl-C/D).
+pub fn run<IO: Io + Default>() -> rustyline::Result<()> {
+    let mut editor: Editor<BfHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(BfHelper));
+
+    let mut debugger: Debugger<IO> = Debugger::new(b"").expect("an empty program always compiles");
+
+    loop {
+        let line = match editor.readline("bf> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(()),
+            Err(error) => return Err(error),
+        };
+        editor.add_history_entry(line.as_str())?;
+
+        match line.strip_prefix(':') {
+            Some(command) => run_command(&mut debugger, command.trim()),
+            None => {
+                // a line the validator held back comes in with the line
+                // breaks typed between prompts, which `compile` rejects
+                let source: Vec<u8> = line.bytes().filter(|&b| b != b'\n').collect();
+                if let Err(error) = debugger.extend(&source) {
+                    println!("compile error: {error:?}");
+                }
+            }
+        }
+    }
+}
+
+fn run_command<IO: Io>(debugger: &mut Debugger<IO>, command: &str) {
+    let mut parts = command.split_whitespace();
+    match parts.next() {
+        Some("break") => match parts.next().and_then(|pc| pc.parse().ok()) {
+            Some(pc) => {
+                debugger.break_at(pc);
+                println!("breakpoint set at {pc}");
+            }
+            None => println!("usage: :break <pc>"),
+        },
+        Some("step") => report(debugger.step()),
+        Some("continue") => report(debugger.continue_()),
+        Some("back") => report(debugger.interpreter.step_back()),
+        Some("run") => report(debugger.run()),
+        Some("tape") => {
+            let radius = parts.next().and_then(|n| n.parse().ok()).unwrap_or(8);
+            println!("{}", debugger.tape_view(radius));
+        }
+        _ => println!("unknown command: :{command}"),
+    }
+}
+
+fn report(result: Result<(), Output>) {
+    match result {
+        Ok(()) => {}
+        Err(error) => println!("{error:?}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::io::BufferIo;
+
+    #[test]
+    fn balance_accepts_matched_brackets() {
+        assert_eq!(brackets_balance("[+[-]]"), Balance::Balanced);
+        assert_eq!(brackets_balance("{ comment }"), Balance::Balanced);
+    }
+
+    #[test]
+    fn balance_waits_on_an_open_bracket() {
+        assert_eq!(brackets_balance("[+"), Balance::Open);
+        assert_eq!(brackets_balance("{ comment"), Balance::Open);
+    }
+
+    #[test]
+    fn balance_rejects_a_stray_closer() {
+        assert_eq!(brackets_balance("+]"), Balance::ClosedTooEarly);
+        assert_eq!(brackets_balance("+}"), Balance::ClosedTooEarly);
+    }
+
+    #[test]
+    fn step_runs_exactly_one_op() {
+        let mut debugger = Debugger::<BufferIo>::new(b"++").unwrap();
+        debugger.step().unwrap();
+        assert_eq!(debugger.interpreter.tape[0], 2); // `++` folds into one Add(2)
+        assert_eq!(debugger.interpreter.pc, 1);
+    }
+
+    #[test]
+    fn continue_stops_at_a_breakpoint() {
+        let mut debugger = Debugger::<BufferIo>::new(b"+++").unwrap();
+        debugger.break_at(1);
+        debugger.continue_().unwrap();
+        assert_eq!(debugger.interpreter.pc, 1);
+    }
+
+    #[test]
+    fn extend_appends_ops_to_the_running_program() {
+        let mut debugger = Debugger::<BufferIo>::new(b"+").unwrap();
+        debugger.extend(b"+").unwrap();
+        debugger.run().unwrap_err();
+        assert_eq!(debugger.interpreter.tape[0], 2);
+    }
+
+    #[test]
+    fn extend_rebases_jump_targets_of_the_appended_program() {
+        let mut debugger = Debugger::<BufferIo>::new(b"+>").unwrap();
+        debugger.extend(b"[->>]").unwrap();
+        assert_eq!(
+            debugger.interpreter.ops[2..],
+            [
+                Op::JumpIfZero(6),
+                Op::Add(255),
+                Op::Move(2),
+                Op::JumpIfNonZero(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn tape_view_marks_the_current_cell() {
+        let debugger = Debugger::<BufferIo>::new(b"").unwrap();
+        assert_eq!(debugger.tape_view(1), "[0] 0  0 ");
+    }
+}